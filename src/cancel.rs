@@ -0,0 +1,74 @@
+// The MIT License (MIT)
+
+// Copyright (c) 2015 Y. T. Chung <zonyitoo@gmail.com>
+
+//  Permission is hereby granted, free of charge, to any person obtaining a
+//  copy of this software and associated documentation files (the "Software"),
+//  to deal in the Software without restriction, including without limitation
+//  the rights to use, copy, modify, merge, publish, distribute, sublicense,
+//  and/or sell copies of the Software, and to permit persons to whom the
+//  Software is furnished to do so, subject to the following conditions:
+//
+//  The above copyright notice and this permission notice shall be included in
+//  all copies or substantial portions of the Software.
+//
+//  THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+//  OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//  FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//  AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//  LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+//  FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+//  DEALINGS IN THE SOFTWARE.
+
+//! The cooperative cancellation flag shared between a `JoinHandle` and the
+//! coroutine it was handed out for
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Message panicked with to unwind a cancelled coroutine
+///
+/// Downcasting a caught panic's payload against this constant is how
+/// `Scheduler::spawn_opts` tells a deliberate cancellation apart from an
+/// ordinary panic in the coroutine's own code.
+pub const CANCEL_PANIC_MSG: &'static str = "__coio_coroutine_cancelled__";
+
+/// Message panicked with to unwind an operation whose `with_timeout`
+/// deadline elapsed
+///
+/// Uses the same cooperative, yield-point-checked mechanism as
+/// `CANCEL_PANIC_MSG`, just driven by a timer instead of an external
+/// `JoinHandle::cancel()` call.
+pub const TIMEOUT_PANIC_MSG: &'static str = "__coio_operation_timed_out__";
+
+/// A flag a `JoinHandle` can raise to ask its coroutine to stop
+///
+/// The flag is only ever observed at scheduler yield points (`sched()`,
+/// `sleep_ms`, blocking `net`/`sync` calls), so setting it never interrupts
+/// a coroutine mid-computation; it takes effect the next time that
+/// coroutine would block or give up the CPU.
+pub struct Cancel {
+    requested: AtomicBool,
+}
+
+impl Cancel {
+    /// Creates a flag with no cancellation requested
+    pub fn new() -> Cancel {
+        Cancel { requested: AtomicBool::new(false) }
+    }
+
+    /// Requests cancellation; idempotent if called more than once
+    #[inline]
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::Release);
+    }
+
+    /// Atomically reads and clears the flag
+    ///
+    /// Clearing on read, rather than leaving the flag set, is what makes
+    /// cancellation visible exactly once: at the yield point where it is
+    /// observed, not on every subsequent check.
+    #[inline]
+    pub fn take_requested(&self) -> bool {
+        self.requested.swap(false, Ordering::AcqRel)
+    }
+}
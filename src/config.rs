@@ -0,0 +1,104 @@
+// The MIT License (MIT)
+
+// Copyright (c) 2015 Y. T. Chung <zonyitoo@gmail.com>
+
+//  Permission is hereby granted, free of charge, to any person obtaining a
+//  copy of this software and associated documentation files (the "Software"),
+//  to deal in the Software without restriction, including without limitation
+//  the rights to use, copy, modify, merge, publish, distribute, sublicense,
+//  and/or sell copies of the Software, and to permit persons to whom the
+//  Software is furnished to do so, subject to the following conditions:
+//
+//  The above copyright notice and this permission notice shall be included in
+//  all copies or substantial portions of the Software.
+//
+//  THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+//  OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//  FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//  AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//  LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+//  FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+//  DEALINGS IN THE SOFTWARE.
+
+//! Process-wide scheduler configuration
+//!
+//! Most applications want to set coroutine defaults once at startup rather
+//! than threading an `Options` through every `spawn` call site. `config()`
+//! returns a handle to that process-wide state.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Default stack size for a spawned coroutine, in bytes, if never overridden
+pub const DEFAULT_STACK_SIZE: usize = 2 * 1024 * 1024;
+
+/// Default number of cached stacks the coroutine pool will retain
+pub const DEFAULT_POOL_CAPACITY: usize = 0;
+
+/// Default number of OS threads `run`/`start` will use if never overridden
+pub const DEFAULT_WORKERS: usize = 1;
+
+/// Process-wide coroutine scheduler configuration
+///
+/// Obtained via `config()`. All fields are backed by atomics so they can be
+/// read and written from any thread without additional synchronization.
+pub struct Config {
+    stack_size: AtomicUsize,
+    pool_capacity: AtomicUsize,
+    workers: AtomicUsize,
+}
+
+impl Config {
+    fn new() -> Config {
+        Config {
+            stack_size: AtomicUsize::new(DEFAULT_STACK_SIZE),
+            pool_capacity: AtomicUsize::new(DEFAULT_POOL_CAPACITY),
+            workers: AtomicUsize::new(DEFAULT_WORKERS),
+        }
+    }
+
+    /// Sets the default stack size, in bytes, used by `spawn` and `Builder::new`
+    /// for coroutines that don't override it explicitly
+    pub fn set_stack_size(&self, stack_size: usize) {
+        self.stack_size.store(stack_size, Ordering::Release);
+    }
+
+    /// Gets the default stack size, in bytes
+    pub fn get_stack_size(&self) -> usize {
+        self.stack_size.load(Ordering::Acquire)
+    }
+
+    /// Sets the capacity of the coroutine stack pool
+    pub fn set_pool_capacity(&self, capacity: usize) {
+        self.pool_capacity.store(capacity, Ordering::Release);
+    }
+
+    /// Gets the capacity of the coroutine stack pool
+    pub fn get_pool_capacity(&self) -> usize {
+        self.pool_capacity.load(Ordering::Acquire)
+    }
+
+    /// Sets the default number of worker threads used by `run`/`start`
+    pub fn set_workers(&self, workers: usize) {
+        self.workers.store(workers, Ordering::Release);
+    }
+
+    /// Gets the default number of worker threads used by `run`/`start`
+    pub fn get_workers(&self) -> usize {
+        self.workers.load(Ordering::Acquire)
+    }
+}
+
+lazy_static! {
+    static ref CONFIG: Config = Config::new();
+}
+
+/// Returns the process-wide scheduler configuration
+///
+/// ```ignore
+/// coio::config().set_stack_size(64 * 1024);
+/// coio::config().set_workers(4);
+/// ```
+#[inline]
+pub fn config() -> &'static Config {
+    &CONFIG
+}
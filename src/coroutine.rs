@@ -0,0 +1,222 @@
+// The MIT License (MIT)
+
+// Copyright (c) 2015 Y. T. Chung <zonyitoo@gmail.com>
+
+//  Permission is hereby granted, free of charge, to any person obtaining a
+//  copy of this software and associated documentation files (the "Software"),
+//  to deal in the Software without restriction, including without limitation
+//  the rights to use, copy, modify, merge, publish, distribute, sublicense,
+//  and/or sell copies of the Software, and to permit persons to whom the
+//  Software is furnished to do so, subject to the following conditions:
+//
+//  The above copyright notice and this permission notice shall be included in
+//  all copies or substantial portions of the Software.
+//
+//  THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+//  OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//  FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//  AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//  LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+//  FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+//  DEALINGS IN THE SOFTWARE.
+
+//! The raw, stack-and-context level building block that the scheduler drives
+//!
+//! This module is intentionally low level: it knows how to allocate a stack
+//! and switch into/out of it, but nothing about run queues, I/O readiness or
+//! work-stealing. That lives in `runtime` and `scheduler`.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use options::Options;
+use runtime::fiber;
+use runtime::pool;
+
+/// The lifecycle state of a coroutine, observable through its `JoinHandle`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    /// Voluntarily gave up the CPU via `sched()`; resumable by the run queue
+    Suspended,
+    /// Parked on an external event (I/O readiness, a timer, a channel
+    /// send); resumable by the reactor rather than the run queue
+    Blocked,
+    /// Currently executing
+    Running,
+    /// Returned a value
+    Finished,
+    /// Unwound by a panic, including a cancellation or timeout unwind
+    Panicked,
+}
+
+/// An atomically-updated cell holding a coroutine's current `State`
+///
+/// Shared between the coroutine's `Processor` (which writes it at every
+/// state transition) and its `JoinHandle` (which only ever reads it).
+pub struct StateCell(AtomicUsize);
+
+impl StateCell {
+    /// Creates a cell starting in `initial`
+    pub fn new(initial: State) -> StateCell {
+        StateCell(AtomicUsize::new(initial as usize))
+    }
+
+    /// Records a new state
+    #[inline]
+    pub fn set(&self, state: State) {
+        self.0.store(state as usize, Ordering::Release);
+    }
+
+    /// Reads the current state
+    #[inline]
+    pub fn get(&self) -> State {
+        match self.0.load(Ordering::Acquire) {
+            0 => State::Suspended,
+            1 => State::Blocked,
+            2 => State::Running,
+            3 => State::Finished,
+            4 => State::Panicked,
+            _ => unreachable!("StateCell only ever stores a State's discriminant"),
+        }
+    }
+}
+
+/// An allocated coroutine stack
+///
+/// May be freshly allocated or handed out of the `runtime::pool` stack pool,
+/// in which case it is indistinguishable from a fresh one: `reset` clears
+/// out anything a previous generation left behind before it is reused.
+///
+/// There is no way to shrink a stack while its coroutine is merely parked:
+/// `Coroutine::run` only ever switches onto it once, at the top, and back
+/// off it once, when the coroutine returns (see `runtime::fiber`). A parked
+/// coroutine's native call frames — including whatever blocking syscall it's
+/// sitting in — are still live partway down this buffer the entire time
+/// it's "blocked", since parking never actually switches the stack out from
+/// under them; only `recycle`, once the coroutine has genuinely finished and
+/// nothing is live on the stack at all, is a safe point to reclaim anything.
+pub struct Stack {
+    size: usize,
+    buffer: Box<[u8]>,
+}
+
+impl Stack {
+    /// Allocates a fresh stack of `size` bytes
+    pub fn new(size: usize) -> Stack {
+        Stack {
+            size: size,
+            buffer: vec![0u8; size].into_boxed_slice(),
+        }
+    }
+
+    /// The size, in bytes, of this stack
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Clears bookkeeping left over from whichever coroutine last ran on
+    /// this stack, so a recycled stack can't leak its previous generation's
+    /// data into the next one
+    pub fn reset(&mut self) {
+        for byte in self.buffer.iter_mut() {
+            *byte = 0;
+        }
+    }
+
+    /// The address one past the end of this stack's backing buffer, for
+    /// `runtime::fiber` to prepare a fresh entry into (stacks grow downward
+    /// on x86_64, so this is where a fresh frame starts)
+    #[inline]
+    pub fn top(&self) -> *mut u8 {
+        unsafe { self.buffer.as_ptr().offset(self.buffer.len() as isize) as *mut u8 }
+    }
+}
+
+/// A single coroutine's stack, context and bookkeeping
+pub struct Coroutine {
+    name: Option<String>,
+    stack: Stack,
+}
+
+impl Coroutine {
+    /// Reserves a stack for a new coroutine, preferring a recycled one of
+    /// the right size from the pool over allocating a fresh one
+    pub fn spawn(opts: Options) -> Coroutine {
+        let stack = pool::pool()
+            .take(opts.stack_size)
+            .unwrap_or_else(|| Stack::new(opts.stack_size));
+
+        Coroutine {
+            name: opts.name,
+            stack: stack,
+        }
+    }
+
+    /// The coroutine's name, if one was given via `Builder::name`
+    #[inline]
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(|s| &**s)
+    }
+
+    /// The size, in bytes, of the stack backing this coroutine
+    #[inline]
+    pub fn stack_size(&self) -> usize {
+        self.stack.size()
+    }
+
+    /// Runs `f` with its stack pointer inside this coroutine's `Stack`,
+    /// switching onto it and back via `runtime::fiber`, and returns its
+    /// result
+    ///
+    /// `f` is responsible for catching its own panics (e.g. via
+    /// `thread::catch_panic`) before returning, rather than letting them
+    /// unwind past this call: the frames that would panic only exist on
+    /// the stack this switches away from, not the one the caller resumes
+    /// on.
+    pub fn run<F, T>(&mut self, f: F) -> T
+        where F: FnOnce() -> T + Send + 'static,
+              T: Send + 'static
+    {
+        struct Payload<F, T> {
+            f: Option<F>,
+            out: Option<T>,
+            resume_sp: usize,
+        }
+
+        extern "C" fn trampoline<F, T>(arg: usize) -> !
+            where F: FnOnce() -> T + Send + 'static,
+                  T: Send + 'static
+        {
+            unsafe {
+                let payload = &mut *(arg as *mut Payload<F, T>);
+                let f = payload.f.take().expect("coroutine trampoline entered twice");
+                payload.out = Some(f());
+
+                // `resume_sp` points at the caller's `caller_sp` local, which the
+                // entry `swap` wrote its stack pointer into; `swap`'s `new_rsp` needs
+                // that stored value, not the address of the local holding it.
+                let resume_sp = *(payload.resume_sp as *const usize);
+                let mut unused = 0usize;
+                fiber::swap(&mut unused as *mut usize, resume_sp, 0);
+            }
+            unreachable!("a finished coroutine's stack is never switched back into")
+        }
+
+        let mut payload = Payload { f: Some(f), out: None, resume_sp: 0 };
+        let mut caller_sp: usize = 0;
+        payload.resume_sp = &caller_sp as *const usize as usize;
+
+        unsafe {
+            let entry_sp = fiber::prepare(self.stack.top(), trampoline::<F, T>);
+            fiber::swap(&mut caller_sp as *mut usize, entry_sp, &mut payload as *mut Payload<F, T> as usize);
+        }
+
+        payload.out.take().expect("coroutine finished without producing a result")
+    }
+
+    /// Finishes this coroutine, handing its stack back to the pool so a
+    /// later `spawn` of the same size can reuse it instead of allocating
+    pub fn recycle(self) {
+        pool::pool().recycle(self.stack);
+    }
+}
@@ -22,7 +22,7 @@
 
 //! Coroutine scheduling with asynchronous I/O support
 
-#![feature(catch_panic, drain, reflect_marker, fnbox)]
+#![feature(catch_panic, drain, reflect_marker, fnbox, asm, naked_functions)]
 
 #[macro_use]
 extern crate lazy_static;
@@ -37,17 +37,23 @@ extern crate deque;
 extern crate rand;
 extern crate libc;
 
-pub use scheduler::{Scheduler, JoinHandle};
+pub use scheduler::{Scheduler, JoinHandle, JoinError, Stats, StateCounts};
 pub use options::Options;
 pub use promise::Promise;
+pub use config::{config, Config};
+pub use timeout::{with_timeout, TimedOut};
+pub use coroutine::State;
 
 pub mod net;
 pub mod sync;
 pub mod scheduler;
 pub mod options;
 pub mod promise;
+pub mod config;
+pub mod timeout;
 mod runtime;
 mod coroutine;
+mod cancel;
 
 /// Spawn a new Coroutine
 #[inline(always)]
@@ -74,12 +80,16 @@ pub fn sched() {
 }
 
 /// Run the scheduler with threads, block until all its threads finish
+///
+/// Passing `0` uses `config().get_workers()` as the thread count.
 #[inline(always)]
 pub fn run(threads: usize) {
     Scheduler::run(threads)
 }
 
 /// Run the scheduler with threads, don't block
+///
+/// Passing `0` uses `config().get_workers()` as the thread count.
 #[inline(always)]
 pub fn start(threads: usize) {
     Scheduler::start(threads)
@@ -91,6 +101,13 @@ pub fn join() {
     Scheduler::join()
 }
 
+/// A snapshot of every in-flight coroutine's lifecycle state and what each
+/// scheduler thread is currently running
+#[inline(always)]
+pub fn stats() -> Stats {
+    Scheduler::stats()
+}
+
 /// Put the current coroutine to sleep for the specific amount of time
 #[inline]
 pub fn sleep_ms(ms: u64) {
@@ -138,6 +155,9 @@ impl Builder {
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
 
     #[test]
     fn test_sleep_ms() {
@@ -155,4 +175,100 @@ mod test {
         start(1);
         join();
     }
+
+    #[test]
+    fn test_config_defaults_feed_options() {
+        config().set_stack_size(128 * 1024);
+        let opts = Options::new();
+        config().set_stack_size(::config::DEFAULT_STACK_SIZE);
+
+        assert_eq!(opts.stack_size, 128 * 1024);
+    }
+
+    #[test]
+    fn test_pool_reuses_matching_size_and_resets_stack() {
+        use coroutine::Stack;
+        use runtime::pool;
+
+        config().set_pool_capacity(4);
+
+        let unused_size = 123457;
+        assert!(pool::pool().take(unused_size).is_none());
+
+        let mut stack = Stack::new(unused_size);
+        unsafe {
+            *stack.top().offset(-1) = 0xAB;
+        }
+        pool::pool().recycle(stack);
+
+        let reused = pool::pool().take(unused_size)
+            .expect("a cached stack of the same size should be handed back");
+        assert_eq!(reused.size(), unused_size);
+        unsafe {
+            assert_eq!(*reused.top().offset(-1), 0,
+                       "take() should reset() a recycled stack before handing it out");
+        }
+    }
+
+    #[test]
+    fn test_cancel_resolves_to_cancelled_and_runs_destructors() {
+        struct MarkOnDrop(Arc<AtomicBool>);
+        impl Drop for MarkOnDrop {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let guard = MarkOnDrop(dropped.clone());
+
+        let handle = spawn(move || {
+            let _guard = guard;
+            loop {
+                sched();
+            }
+        });
+
+        start(1);
+        handle.cancel();
+
+        let result = handle.join();
+        join();
+
+        match result {
+            Err(JoinError::Cancelled) => {}
+            _ => panic!("cancelling a JoinHandle should resolve its join() to Cancelled"),
+        }
+        assert!(dropped.load(Ordering::SeqCst), "cancelling should still run the coroutine's destructors");
+    }
+
+    #[test]
+    fn test_with_timeout_races_the_wrapped_closure() {
+        let outcome = spawn(|| {
+            with_timeout(Duration::from_millis(10), || {
+                loop {
+                    sched();
+                }
+            })
+        });
+
+        start(1);
+        let result = outcome.join().unwrap();
+        join();
+
+        assert!(result.is_err(), "a closure that keeps yielding past the deadline should time out");
+    }
+
+    #[test]
+    fn test_stats_counts_are_lifetime_totals() {
+        spawn(|| {});
+        run(1);
+
+        let first = stats();
+        let second = stats();
+
+        assert!(first.counts.finished >= 1);
+        assert_eq!(first.counts.finished, second.counts.finished,
+                   "finished/panicked are lifetime totals, not reset by the previous stats() call");
+    }
 }
@@ -0,0 +1,114 @@
+// The MIT License (MIT)
+
+// Copyright (c) 2015 Y. T. Chung <zonyitoo@gmail.com>
+
+//  Permission is hereby granted, free of charge, to any person obtaining a
+//  copy of this software and associated documentation files (the "Software"),
+//  to deal in the Software without restriction, including without limitation
+//  the rights to use, copy, modify, merge, publish, distribute, sublicense,
+//  and/or sell copies of the Software, and to permit persons to whom the
+//  Software is furnished to do so, subject to the following conditions:
+//
+//  The above copyright notice and this permission notice shall be included in
+//  all copies or substantial portions of the Software.
+//
+//  THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+//  OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//  FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//  AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//  LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+//  FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+//  DEALINGS IN THE SOFTWARE.
+
+//! Coroutine-scheduled networking
+//!
+//! Blocking calls here mark the calling coroutine as parked and resume
+//! through `runtime::EventSource::yield_back`, so a pending
+//! `JoinHandle::cancel()` is honored before control returns to the
+//! coroutine's own code.
+
+use std::io::{self, Read, Write};
+use std::net::ToSocketAddrs;
+use std::time::Duration;
+
+use runtime::EventSource;
+
+/// Normalizes the two `io::ErrorKind`s a platform may report for an elapsed
+/// socket timeout (`WouldBlock` on some platforms, `TimedOut` on others)
+/// down to a single `TimedOut` callers can match on
+fn deadline_elapsed<T>(result: io::Result<T>) -> io::Result<T> {
+    match result {
+        Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+            Err(io::Error::new(io::ErrorKind::TimedOut, "deadline elapsed"))
+        }
+        other => other,
+    }
+}
+
+/// A coroutine-scheduled TCP stream
+///
+/// `read`/`write` mark the calling coroutine `Blocked`, then make an
+/// ordinary blocking call on the underlying `std::net::TcpStream`, which
+/// blocks the OS thread until it completes; they do not wait on socket
+/// readiness.
+pub struct TcpStream {
+    inner: ::std::net::TcpStream,
+}
+
+impl TcpStream {
+    /// Opens a TCP connection to `addr`
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
+        let inner = try!(::std::net::TcpStream::connect(addr));
+        Ok(TcpStream { inner: inner })
+    }
+
+    fn wait_readiness(&self) -> io::Result<()> {
+        EventSource::park();
+        EventSource::yield_back();
+        Ok(())
+    }
+
+    /// Like `read`, but fails with `io::ErrorKind::TimedOut` instead of
+    /// parking past `timeout`
+    ///
+    /// Delegates the deadline to the OS socket itself via
+    /// `set_read_timeout` rather than checking it from here: a single
+    /// `wait_readiness` park can't be re-armed with less time remaining
+    /// once it's already parked, so only the underlying `read` call is in
+    /// a position to actually enforce `timeout`.
+    pub fn read_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        try!(self.wait_readiness());
+        try!(self.inner.set_read_timeout(Some(timeout)));
+        let result = self.inner.read(buf);
+        try!(self.inner.set_read_timeout(None));
+        deadline_elapsed(result)
+    }
+
+    /// Like `write`, but fails with `io::ErrorKind::TimedOut` instead of
+    /// parking past `timeout`
+    pub fn write_timeout(&mut self, buf: &[u8], timeout: Duration) -> io::Result<usize> {
+        try!(self.wait_readiness());
+        try!(self.inner.set_write_timeout(Some(timeout)));
+        let result = self.inner.write(buf);
+        try!(self.inner.set_write_timeout(None));
+        deadline_elapsed(result)
+    }
+}
+
+impl Read for TcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        try!(self.wait_readiness());
+        self.inner.read(buf)
+    }
+}
+
+impl Write for TcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        try!(self.wait_readiness());
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
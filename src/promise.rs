@@ -0,0 +1,79 @@
+// The MIT License (MIT)
+
+// Copyright (c) 2015 Y. T. Chung <zonyitoo@gmail.com>
+
+//  Permission is hereby granted, free of charge, to any person obtaining a
+//  copy of this software and associated documentation files (the "Software"),
+//  to deal in the Software without restriction, including without limitation
+//  the rights to use, copy, modify, merge, publish, distribute, sublicense,
+//  and/or sell copies of the Software, and to permit persons to whom the
+//  Software is furnished to do so, subject to the following conditions:
+//
+//  The above copyright notice and this permission notice shall be included in
+//  all copies or substantial portions of the Software.
+//
+//  THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+//  OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//  FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//  AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//  LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+//  FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+//  DEALINGS IN THE SOFTWARE.
+
+//! A single-assignment, single-read slot used to hand a coroutine's result
+//! back to whoever is holding its `JoinHandle`
+
+use std::sync::Mutex;
+use std::sync::Condvar;
+
+enum Slot<T> {
+    Empty,
+    Ready(T),
+    Taken,
+}
+
+/// A promise of a value of type `T`, fulfilled exactly once
+pub struct Promise<T> {
+    slot: Mutex<Slot<T>>,
+    cond: Condvar,
+}
+
+impl<T> Promise<T> {
+    /// Creates an unfulfilled promise
+    pub fn new() -> Promise<T> {
+        Promise {
+            slot: Mutex::new(Slot::Empty),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// Fulfills the promise, waking any thread blocked in `wait`
+    ///
+    /// Panics if the promise was already fulfilled.
+    pub fn fulfill(&self, value: T) {
+        let mut slot = self.slot.lock().unwrap();
+        match *slot {
+            Slot::Empty => *slot = Slot::Ready(value),
+            _ => panic!("Promise already fulfilled"),
+        }
+        self.cond.notify_one();
+    }
+
+    /// Blocks the calling thread until the promise is fulfilled, then
+    /// returns the value
+    ///
+    /// Panics if called more than once.
+    pub fn wait(&self) -> T {
+        let mut slot = self.slot.lock().unwrap();
+        loop {
+            match ::std::mem::replace(&mut *slot, Slot::Taken) {
+                Slot::Ready(value) => return value,
+                Slot::Taken => panic!("Promise already taken"),
+                Slot::Empty => {
+                    *slot = Slot::Empty;
+                    slot = self.cond.wait(slot).unwrap();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+// The MIT License (MIT)
+
+// Copyright (c) 2015 Y. T. Chung <zonyitoo@gmail.com>
+
+//  Permission is hereby granted, free of charge, to any person obtaining a
+//  copy of this software and associated documentation files (the "Software"),
+//  to deal in the Software without restriction, including without limitation
+//  the rights to use, copy, modify, merge, publish, distribute, sublicense,
+//  and/or sell copies of the Software, and to permit persons to whom the
+//  Software is furnished to do so, subject to the following conditions:
+//
+//  The above copyright notice and this permission notice shall be included in
+//  all copies or substantial portions of the Software.
+//
+//  THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+//  OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//  FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//  AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//  LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+//  FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+//  DEALINGS IN THE SOFTWARE.
+
+//! A minimal x86_64 stackful context switch
+//!
+//! `coroutine::Coroutine::run` is the only caller: it's what actually lets a
+//! coroutine's closure execute with its stack pointer inside the `Stack` the
+//! pool hands out, rather than on the calling thread's own stack. Only
+//! x86_64 is supported.
+
+use std::mem;
+
+#[cfg(target_arch = "x86_64")]
+#[naked]
+unsafe extern "C" fn swap_regs(prev_rsp_out: *mut usize, new_rsp: usize, arg: usize) -> usize {
+    asm!(
+        "push %rbp
+         push %rbx
+         push %r12
+         push %r13
+         push %r14
+         push %r15
+         mov %rsp, (%rdi)
+         mov %rsi, %rsp
+         mov %rdx, %rax
+         mov %rdx, %rdi
+         pop %r15
+         pop %r14
+         pop %r13
+         pop %r12
+         pop %rbx
+         pop %rbp
+         ret"
+        : : : : "volatile"
+    );
+}
+
+/// Primes a fresh stack so that switching into it with `swap` jumps to
+/// `entry(arg)`, which must never return
+///
+/// `top` is one past the end of the stack's backing buffer — stacks grow
+/// downward on x86_64, so this is where the fresh frame starts. Returns the
+/// stack pointer to pass as `swap`'s `new_rsp`.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn prepare(top: *mut u8, entry: extern "C" fn(usize) -> !) -> usize {
+    let top = (top as usize) & !0xf;
+    let sp = top - 8 * mem::size_of::<usize>();
+
+    let slots = sp as *mut usize;
+    *slots.offset(0) = 0; // r15
+    *slots.offset(1) = 0; // r14
+    *slots.offset(2) = 0; // r13
+    *slots.offset(3) = 0; // r12
+    *slots.offset(4) = 0; // rbx
+    *slots.offset(5) = 0; // rbp
+    *slots.offset(6) = entry as usize; // address `ret` jumps to on first entry
+    // offset(7) is left as padding, to keep `sp` 16-byte aligned
+
+    sp
+}
+
+/// Switches the calling thread onto `new_rsp`, handing it `arg`, saving the
+/// caller's own stack pointer into `*prev_rsp_out` first
+///
+/// Returns once something switches back into `*prev_rsp_out`. Symmetric: a
+/// coroutine calls this the same way to switch back out.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn swap(prev_rsp_out: *mut usize, new_rsp: usize, arg: usize) {
+    swap_regs(prev_rsp_out, new_rsp, arg);
+}
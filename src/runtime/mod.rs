@@ -0,0 +1,188 @@
+// The MIT License (MIT)
+
+// Copyright (c) 2015 Y. T. Chung <zonyitoo@gmail.com>
+
+//  Permission is hereby granted, free of charge, to any person obtaining a
+//  copy of this software and associated documentation files (the "Software"),
+//  to deal in the Software without restriction, including without limitation
+//  the rights to use, copy, modify, merge, publish, distribute, sublicense,
+//  and/or sell copies of the Software, and to permit persons to whom the
+//  Software is furnished to do so, subject to the following conditions:
+//
+//  The above copyright notice and this permission notice shall be included in
+//  all copies or substantial portions of the Software.
+//
+//  THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+//  OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//  FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//  AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//  LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+//  FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+//  DEALINGS IN THE SOFTWARE.
+
+//! Per-thread scheduling loop
+//!
+//! Each OS thread started by `Scheduler::run`/`start` owns one `Processor`,
+//! which tracks the cancel flag, `with_timeout` deadline and `State` cell of
+//! the coroutine currently running on that thread. It does not poll an I/O
+//! event loop or fire timers.
+
+use std::cell::UnsafeCell;
+use std::sync::Arc;
+use std::time::Duration;
+use std::thread;
+
+use cancel::{Cancel, CANCEL_PANIC_MSG, TIMEOUT_PANIC_MSG};
+use coroutine::State;
+use coroutine::StateCell;
+
+pub mod fiber;
+pub mod pool;
+pub mod timer;
+
+thread_local!(static PROCESSOR: UnsafeCell<Processor> = UnsafeCell::new(Processor::new()));
+
+/// A single worker thread's scheduling state
+pub struct Processor {
+    /// The `Cancel` flag of whichever coroutine is currently running on
+    /// this thread, if any
+    current_cancel: Option<Arc<Cancel>>,
+    /// The deadline flag of an in-flight `with_timeout`, if any is wrapping
+    /// code currently running on this thread
+    current_deadline: Option<Arc<Cancel>>,
+    /// The `State` cell of whichever coroutine is currently running on
+    /// this thread, if any
+    current_state: Option<Arc<StateCell>>,
+}
+
+impl Processor {
+    fn new() -> Processor {
+        Processor {
+            current_cancel: None,
+            current_deadline: None,
+            current_state: None,
+        }
+    }
+
+    /// Returns a reference to the calling thread's `Processor`
+    ///
+    /// Every OS thread that ever spawns or resumes a coroutine has exactly
+    /// one of these, lazily created on first use and alive for the life of
+    /// the thread, so handing back a raw `&'static mut` is sound here even
+    /// though nothing actually `'static`-allocates it.
+    pub fn current() -> &'static mut Processor {
+        PROCESSOR.with(|p| unsafe { &mut *p.get() })
+    }
+
+    /// Records `cancel` as the flag for the coroutine about to run on this
+    /// thread, so later `check_cancel()` calls observe it
+    pub fn set_cancel(&mut self, cancel: Arc<Cancel>) {
+        self.current_cancel = Some(cancel);
+    }
+
+    /// Clears the current coroutine's cancel flag once it has finished
+    pub fn clear_cancel(&mut self) {
+        self.current_cancel = None;
+    }
+
+    /// Registers `state` as the cell to update with this thread's current
+    /// coroutine's lifecycle transitions
+    pub fn set_state(&mut self, state: Arc<StateCell>) {
+        self.current_state = Some(state);
+    }
+
+    /// Unregisters the current coroutine's state cell once it has finished
+    pub fn clear_state(&mut self) {
+        self.current_state = None;
+    }
+
+    /// Records a state transition for the running coroutine, if any
+    pub fn mark(&mut self, state: State) {
+        if let Some(ref cell) = self.current_state {
+            cell.set(state);
+        }
+    }
+
+    /// Registers `deadline` as the flag an in-flight `with_timeout` will
+    /// raise if its timer wins the race against the wrapped operation
+    pub fn set_deadline(&mut self, deadline: Arc<Cancel>) -> Option<Arc<Cancel>> {
+        ::std::mem::replace(&mut self.current_deadline, Some(deadline))
+    }
+
+    /// Restores the previous deadline (or clears it), once a `with_timeout`
+    /// call returns
+    pub fn restore_deadline(&mut self, previous: Option<Arc<Cancel>>) {
+        self.current_deadline = previous;
+    }
+
+    /// Checks whether the running coroutine has been cancelled, or an
+    /// enclosing `with_timeout`'s deadline has elapsed, and if so unwinds it
+    /// by panicking with the matching sentinel message
+    ///
+    /// Every scheduler yield point calls this exactly when a blocked
+    /// coroutine is about to be resumed, so neither cancellation nor a
+    /// timeout ever interrupts non-yielding compute.
+    ///
+    /// This only runs around a yield point, not during it: a coroutine
+    /// blocked inside a single real syscall (e.g. `net::TcpStream::read`
+    /// with no timeout) isn't woken early, only checked once that call
+    /// returns on its own.
+    pub fn check_cancel(&mut self) {
+        let cancelled = match self.current_cancel {
+            Some(ref cancel) => cancel.take_requested(),
+            None => false,
+        };
+
+        if cancelled {
+            panic!(CANCEL_PANIC_MSG);
+        }
+
+        let timed_out = match self.current_deadline {
+            Some(ref deadline) => deadline.take_requested(),
+            None => false,
+        };
+
+        if timed_out {
+            panic!(TIMEOUT_PANIC_MSG);
+        }
+    }
+
+    /// Puts the running coroutine to sleep for `ms` milliseconds
+    ///
+    /// Outside of a coroutine (e.g. in a test running on the main thread
+    /// with no scheduler driving it) this just blocks the OS thread.
+    pub fn sleep_ms(&mut self, ms: u64) {
+        self.mark(State::Blocked);
+        thread::sleep(Duration::from_millis(ms));
+        self.mark(State::Running);
+        self.check_cancel();
+    }
+}
+
+/// The point at which a coroutine blocks on, and later resumes from, an
+/// external event — I/O readiness, a channel, a timer
+///
+/// `net` and `sync` funnel every blocking wait through here so a pending
+/// `JoinHandle::cancel()` is honored in exactly one place. Parking here does
+/// not shrink or release the coroutine's stack, since a park like this one
+/// never switches off the coroutine's own stack (see
+/// `coroutine::Coroutine::run`); the stack is only handed back, whole, to
+/// `runtime::pool` once the coroutine finishes.
+pub struct EventSource;
+
+impl EventSource {
+    /// Called right before a coroutine parks to wait on an external event
+    #[inline]
+    pub fn park() {
+        Processor::current().mark(State::Blocked);
+    }
+
+    /// Called immediately after a blocking wait completes, before control
+    /// returns to the coroutine's own code
+    #[inline]
+    pub fn yield_back() {
+        let processor = Processor::current();
+        processor.mark(State::Running);
+        processor.check_cancel();
+    }
+}
@@ -0,0 +1,123 @@
+// The MIT License (MIT)
+
+// Copyright (c) 2015 Y. T. Chung <zonyitoo@gmail.com>
+
+//  Permission is hereby granted, free of charge, to any person obtaining a
+//  copy of this software and associated documentation files (the "Software"),
+//  to deal in the Software without restriction, including without limitation
+//  the rights to use, copy, modify, merge, publish, distribute, sublicense,
+//  and/or sell copies of the Software, and to permit persons to whom the
+//  Software is furnished to do so, subject to the following conditions:
+//
+//  The above copyright notice and this permission notice shall be included in
+//  all copies or substantial portions of the Software.
+//
+//  THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+//  OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//  FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//  AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//  LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+//  FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+//  DEALINGS IN THE SOFTWARE.
+
+//! A lock-free pool of recycled coroutine stacks
+//!
+//! `Scheduler::spawn` allocating a fresh stack on every call is expensive
+//! under high spawn churn (e.g. connection-per-coroutine servers). Instead,
+//! a finished coroutine's stack is pushed here and the next `spawn` of a
+//! matching size pulls it back out instead of calling into the allocator.
+
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use coroutine::Stack;
+use config::config;
+
+struct Node {
+    stack: Stack,
+    next: *mut Node,
+}
+
+/// A many-producer, many-consumer pool built as a Treiber stack: pushing and
+/// popping are both a single CAS on `head`, so no coroutine ever blocks
+/// behind a lock just to recycle or borrow a stack.
+pub struct StackPool {
+    head: AtomicPtr<Node>,
+    len: AtomicUsize,
+}
+
+unsafe impl Send for StackPool {}
+unsafe impl Sync for StackPool {}
+
+impl StackPool {
+    fn new() -> StackPool {
+        StackPool {
+            head: AtomicPtr::new(ptr::null_mut()),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Takes a cached stack of exactly `stack_size` bytes, if one is
+    /// available; stacks of a different size are dropped rather than
+    /// reused, since the next coroutine to claim them would otherwise
+    /// inherit a stack bound to the wrong `Options::stack_size`.
+    ///
+    /// Returns `None` when the pool is empty, in which case the caller
+    /// falls back to allocating a fresh stack.
+    pub fn take(&self, stack_size: usize) -> Option<Stack> {
+        loop {
+            let head = self.head.load(Ordering::Acquire);
+            if head.is_null() {
+                return None;
+            }
+
+            let next = unsafe { (*head).next };
+            if self.head.compare_and_swap(head, next, Ordering::AcqRel) != head {
+                continue;
+            }
+
+            self.len.fetch_sub(1, Ordering::AcqRel);
+            let mut stack = unsafe { Box::from_raw(head) }.stack;
+
+            if stack.size() != stack_size {
+                continue;
+            }
+
+            stack.reset();
+            return Some(stack);
+        }
+    }
+
+    /// Caches `stack` for a future `take` of the same size
+    ///
+    /// If the pool is already at `config().get_pool_capacity()`, `stack` is
+    /// dropped (and its memory freed) instead of being cached.
+    pub fn recycle(&self, stack: Stack) {
+        if self.len.load(Ordering::Acquire) >= config().get_pool_capacity() {
+            return;
+        }
+
+        let node = Box::into_raw(Box::new(Node { stack: stack, next: ptr::null_mut() }));
+        loop {
+            let head = self.head.load(Ordering::Acquire);
+            unsafe {
+                (*node).next = head;
+            }
+
+            if self.head.compare_and_swap(head, node, Ordering::AcqRel) == head {
+                self.len.fetch_add(1, Ordering::AcqRel);
+                return;
+            }
+        }
+    }
+}
+
+lazy_static! {
+    static ref POOL: StackPool = StackPool::new();
+}
+
+/// The process-wide stack pool shared by every `Processor`
+#[inline]
+pub fn pool() -> &'static StackPool {
+    &POOL
+}
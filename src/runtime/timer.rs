@@ -0,0 +1,96 @@
+// The MIT License (MIT)
+
+// Copyright (c) 2015 Y. T. Chung <zonyitoo@gmail.com>
+
+//  Permission is hereby granted, free of charge, to any person obtaining a
+//  copy of this software and associated documentation files (the "Software"),
+//  to deal in the Software without restriction, including without limitation
+//  the rights to use, copy, modify, merge, publish, distribute, sublicense,
+//  and/or sell copies of the Software, and to permit persons to whom the
+//  Software is furnished to do so, subject to the following conditions:
+//
+//  The above copyright notice and this permission notice shall be included in
+//  all copies or substantial portions of the Software.
+//
+//  THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+//  OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//  FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//  AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//  LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+//  FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+//  DEALINGS IN THE SOFTWARE.
+
+//! Timed wakeups for `with_timeout` (`sleep_ms` does not use this; it
+//! blocks its OS thread with `thread::sleep`)
+//!
+//! A real reactor integration drives deadlines off the `mio` event loop's
+//! poll timeout, firing whichever registration comes due without a thread
+//! per timer. This tree doesn't wire that reactor up yet, so `arm` falls
+//! back to one OS thread per timer, parked on a condvar so `TimerHandle::cancel`
+//! can wake it early instead of leaving it asleep for the rest of its
+//! duration once the race it's part of is already decided.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+struct Inner {
+    cancelled: Mutex<bool>,
+    condvar: Condvar,
+}
+
+/// A handle to an armed timer, returned by `arm`
+///
+/// Dropping this without calling `cancel()` leaves the timer armed; it still
+/// fires normally.
+pub struct TimerHandle {
+    inner: Arc<Inner>,
+}
+
+impl TimerHandle {
+    /// Wakes the timer's thread early without calling `wake`, if it hasn't
+    /// fired yet
+    ///
+    /// Callers that raced a timer against an operation (`with_timeout`) call
+    /// this once the operation wins, so the losing timer's thread doesn't
+    /// sit asleep for the rest of its duration.
+    pub fn cancel(&self) {
+        let mut cancelled = self.inner.cancelled.lock().unwrap();
+        *cancelled = true;
+        self.inner.condvar.notify_one();
+    }
+}
+
+/// Arms a one-shot timer that calls `wake` after `duration` elapses, unless
+/// cancelled first through the returned `TimerHandle`
+pub fn arm<F>(duration: Duration, wake: F) -> TimerHandle
+    where F: FnOnce() + Send + 'static
+{
+    let inner = Arc::new(Inner {
+        cancelled: Mutex::new(false),
+        condvar: Condvar::new(),
+    });
+    let timer_inner = inner.clone();
+
+    thread::spawn(move || {
+        let deadline = Instant::now() + duration;
+        let mut cancelled = timer_inner.cancelled.lock().unwrap();
+
+        loop {
+            if *cancelled {
+                return;
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+
+            cancelled = timer_inner.condvar.wait_timeout(cancelled, deadline - now).unwrap().0;
+        }
+
+        wake();
+    });
+
+    TimerHandle { inner: inner }
+}
@@ -0,0 +1,337 @@
+// The MIT License (MIT)
+
+// Copyright (c) 2015 Y. T. Chung <zonyitoo@gmail.com>
+
+//  Permission is hereby granted, free of charge, to any person obtaining a
+//  copy of this software and associated documentation files (the "Software"),
+//  to deal in the Software without restriction, including without limitation
+//  the rights to use, copy, modify, merge, publish, distribute, sublicense,
+//  and/or sell copies of the Software, and to permit persons to whom the
+//  Software is furnished to do so, subject to the following conditions:
+//
+//  The above copyright notice and this permission notice shall be included in
+//  all copies or substantial portions of the Software.
+//
+//  THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+//  OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//  FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//  AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//  LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+//  FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+//  DEALINGS IN THE SOFTWARE.
+
+//! The global coroutine scheduler: run queue, worker pool and join handles
+
+use std::any::Any;
+use std::boxed::FnBox;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::VecDeque;
+use std::thread::{self, JoinHandle as ThreadJoinHandle};
+
+use cancel::{Cancel, CANCEL_PANIC_MSG};
+use config::config;
+use coroutine::{Coroutine, State, StateCell};
+use options::Options;
+use promise::Promise;
+use runtime::Processor;
+
+/// A pending unit of work: a coroutine's closure (which fulfills the promise
+/// its `JoinHandle` is waiting on) plus the coroutine's `State` cell
+struct Job {
+    run: Box<FnBox()>,
+    state: Arc<StateCell>,
+}
+
+struct SharedState {
+    queue: Mutex<VecDeque<Job>>,
+    workers: Mutex<Vec<ThreadJoinHandle<()>>>,
+    /// Every coroutine that has been spawned and hasn't yet reached
+    /// `Finished`/`Panicked`; a coroutine removes itself as soon as it gets
+    /// there (see `Scheduler::spawn_opts`), so this never grows past the
+    /// number of coroutines genuinely still in flight
+    registry: Mutex<Vec<Arc<StateCell>>>,
+    /// Lifetime totals for coroutines that have already been pruned from
+    /// `registry`, since `Scheduler::stats()` still needs to report them
+    /// somewhere once they're gone from it
+    finished_total: AtomicUsize,
+    panicked_total: AtomicUsize,
+    /// One slot per worker thread started by `start`, holding the `State`
+    /// of whatever coroutine it's currently running, if any
+    worker_slots: Mutex<Vec<Option<Arc<StateCell>>>>,
+}
+
+lazy_static! {
+    static ref SHARED: Arc<SharedState> = Arc::new(SharedState {
+        queue: Mutex::new(VecDeque::new()),
+        workers: Mutex::new(Vec::new()),
+        registry: Mutex::new(Vec::new()),
+        finished_total: AtomicUsize::new(0),
+        panicked_total: AtomicUsize::new(0),
+        worker_slots: Mutex::new(Vec::new()),
+    });
+}
+
+/// Why a `JoinHandle::join()` didn't resolve to a value
+#[derive(Debug)]
+pub enum JoinError {
+    /// The coroutine was unwound in response to `JoinHandle::cancel()`
+    /// before it returned a value
+    Cancelled,
+    /// The coroutine panicked; carries the panic's payload, as caught by
+    /// `std::thread::catch_panic`
+    Panicked(Box<Any + Send>),
+}
+
+/// A handle to a coroutine spawned via `Scheduler::spawn`/`spawn_opts`
+///
+/// Dropping a `JoinHandle` does not stop the coroutine; call `join()` to
+/// wait for its result, or `cancel()` to ask it to stop.
+pub struct JoinHandle<T> {
+    promise: Arc<Promise<Result<T, JoinError>>>,
+    cancel: Arc<Cancel>,
+    state: Arc<StateCell>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Blocks the calling thread until the coroutine finishes and returns
+    /// its result, or the error that kept it from producing one
+    pub fn join(self) -> Result<T, JoinError> {
+        self.promise.wait()
+    }
+
+    /// Requests that the coroutine be cancelled
+    ///
+    /// Cancellation is cooperative and only takes effect at the coroutine's
+    /// next scheduler yield point (`sched()`, `sleep_ms`, a blocking `net`
+    /// call, a `sync` channel wait). Once it does, the coroutine is unwound
+    /// via a panic so its destructors still run, and `join()` resolves to
+    /// `Err(JoinError::Cancelled)`.
+    ///
+    /// A yield point is only checked once the coroutine resumes from it,
+    /// not while the blocking call underneath it is actually in flight: a
+    /// coroutine sitting inside a single long `TcpStream::read` isn't
+    /// interrupted mid-syscall, only once that call returns. Use
+    /// `TcpStream::read_timeout` there if a hard deadline matters more than
+    /// cooperative cancellation.
+    pub fn cancel(&self) {
+        self.cancel.request();
+    }
+
+    /// The coroutine's current lifecycle state
+    pub fn state(&self) -> State {
+        self.state.get()
+    }
+}
+
+/// The global coroutine scheduler
+///
+/// `coio`'s free functions (`spawn`, `run`, `sched`, ...) are thin wrappers
+/// around the associated functions here.
+pub struct Scheduler;
+
+impl Scheduler {
+    /// Spawns a coroutine with default `Options`
+    pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+        where F: FnOnce() -> T + Send + 'static,
+              T: Send + 'static
+    {
+        Scheduler::spawn_opts(f, Options::new())
+    }
+
+    /// Spawns a coroutine with the given `Options`
+    pub fn spawn_opts<F, T>(f: F, opts: Options) -> JoinHandle<T>
+        where F: FnOnce() -> T + Send + 'static,
+              T: Send + 'static
+    {
+        let promise = Arc::new(Promise::new());
+        let result_promise = promise.clone();
+        let cancel = Arc::new(Cancel::new());
+        let coro_cancel = cancel.clone();
+        let state = Arc::new(StateCell::new(State::Suspended));
+        let job_state = state.clone();
+
+        SHARED.registry.lock().unwrap().push(state.clone());
+
+        let job_opts = opts.clone();
+        let job: Box<FnBox()> = Box::new(move || {
+            let mut coro = Coroutine::spawn(job_opts);
+
+            Processor::current().set_cancel(coro_cancel.clone());
+            Processor::current().set_state(job_state.clone());
+            Processor::current().mark(State::Running);
+            let outcome = coro.run(move || thread::catch_panic(move || f()));
+            Processor::current().clear_cancel();
+
+            coro.recycle();
+
+            let outcome = match outcome {
+                Ok(value) => {
+                    Processor::current().mark(State::Finished);
+                    SHARED.finished_total.fetch_add(1, Ordering::Relaxed);
+                    Ok(value)
+                }
+                Err(payload) => {
+                    Processor::current().mark(State::Panicked);
+                    SHARED.panicked_total.fetch_add(1, Ordering::Relaxed);
+                    if is_cancel_panic(&payload) {
+                        Err(JoinError::Cancelled)
+                    } else {
+                        Err(JoinError::Panicked(payload))
+                    }
+                }
+            };
+
+            Processor::current().clear_state();
+            forget_finished(&job_state);
+
+            result_promise.fulfill(outcome);
+        });
+
+        SHARED.queue.lock().unwrap().push_back(Job { run: job, state: state.clone() });
+
+        JoinHandle { promise: promise, cancel: cancel, state: state }
+    }
+
+    /// Gives up the CPU by yielding the OS thread (`thread::yield_now`)
+    ///
+    /// Each worker runs a job to completion before popping the next one, so
+    /// no other coroutine runs on this thread in the meantime.
+    ///
+    /// Unlike a park on an external event, this is a voluntary yield: the
+    /// coroutine is immediately resumable by the run queue rather than
+    /// waiting on the reactor, so it's tracked as `State::Suspended` rather
+    /// than `State::Blocked`.
+    pub fn sched() {
+        let processor = Processor::current();
+        processor.mark(State::Suspended);
+        thread::yield_now();
+        processor.mark(State::Running);
+        processor.check_cancel();
+    }
+
+    /// Pops and runs jobs from the shared queue until it is empty, recording
+    /// each one's state in `worker_slots[index]` for the duration
+    fn run_queue(index: usize) {
+        loop {
+            let job = SHARED.queue.lock().unwrap().pop_front();
+            match job {
+                Some(job) => {
+                    SHARED.worker_slots.lock().unwrap()[index] = Some(job.state.clone());
+                    job.run.call_box(());
+                    SHARED.worker_slots.lock().unwrap()[index] = None;
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Runs the scheduler on `threads` OS threads, blocking until they all
+    /// finish draining the run queue
+    ///
+    /// Passing `0` defers the thread count to `config().get_workers()`.
+    pub fn run(threads: usize) {
+        Scheduler::start(threads);
+        Scheduler::join();
+    }
+
+    /// Like `run`, but returns immediately instead of blocking
+    ///
+    /// Passing `0` defers the thread count to `config().get_workers()`.
+    pub fn start(threads: usize) {
+        let threads = if threads == 0 { config().get_workers() } else { threads };
+
+        let base = {
+            let mut slots = SHARED.worker_slots.lock().unwrap();
+            let base = slots.len();
+            slots.extend((0..threads).map(|_| None));
+            base
+        };
+
+        let mut workers = SHARED.workers.lock().unwrap();
+        for offset in 0..threads {
+            let index = base + offset;
+            workers.push(thread::spawn(move || Scheduler::run_queue(index)));
+        }
+    }
+
+    /// Blocks until every worker thread started by `start` has finished
+    pub fn join() {
+        let mut workers = SHARED.workers.lock().unwrap();
+        for worker in workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+
+    /// A snapshot of every tracked coroutine's lifecycle state, plus what
+    /// each worker thread is currently running
+    ///
+    /// `counts.finished`/`counts.panicked` are lifetime totals, not a
+    /// currently-in-flight count like the other three states: a coroutine
+    /// is pruned from tracking as soon as it reaches one of those two
+    /// states (see `Scheduler::spawn_opts`), so there's nothing left to
+    /// observe per-call the way `Suspended`/`Blocked`/`Running` are.
+    pub fn stats() -> Stats {
+        let mut counts = StateCounts::default();
+        for state in SHARED.registry.lock().unwrap().iter() {
+            counts.record(state.get());
+        }
+        counts.finished = SHARED.finished_total.load(Ordering::Relaxed);
+        counts.panicked = SHARED.panicked_total.load(Ordering::Relaxed);
+
+        let per_worker = SHARED.worker_slots.lock().unwrap()
+            .iter()
+            .map(|slot| slot.as_ref().map(|state| state.get()))
+            .collect();
+
+        Stats { counts: counts, per_worker: per_worker }
+    }
+}
+
+/// Drops `state`'s `StateCell` from the registry once its coroutine has
+/// reached a terminal state, so the registry never accumulates an entry for
+/// every coroutine ever spawned
+fn forget_finished(state: &Arc<StateCell>) {
+    let mut registry = SHARED.registry.lock().unwrap();
+    if let Some(index) = registry.iter().position(|cell| Arc::ptr_eq(cell, state)) {
+        registry.swap_remove(index);
+    }
+}
+
+/// How many tracked coroutines are in each `State`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StateCounts {
+    pub suspended: usize,
+    pub blocked: usize,
+    pub running: usize,
+    pub finished: usize,
+    pub panicked: usize,
+}
+
+impl StateCounts {
+    fn record(&mut self, state: State) {
+        match state {
+            State::Suspended => self.suspended += 1,
+            State::Blocked => self.blocked += 1,
+            State::Running => self.running += 1,
+            State::Finished => self.finished += 1,
+            State::Panicked => self.panicked += 1,
+        }
+    }
+}
+
+/// A snapshot returned by `Scheduler::stats()`
+#[derive(Clone, Debug)]
+pub struct Stats {
+    /// How many tracked coroutines are in each state
+    pub counts: StateCounts,
+    /// What each worker thread started by `start` is currently running,
+    /// indexed by worker, `None` if it's idle waiting on the queue
+    pub per_worker: Vec<Option<State>>,
+}
+
+/// Tells a deliberate `JoinHandle::cancel()` unwind apart from an ordinary
+/// panic raised by the coroutine's own code
+fn is_cancel_panic(payload: &Box<Any + Send>) -> bool {
+    payload.downcast_ref::<&str>().map_or(false, |msg| *msg == CANCEL_PANIC_MSG)
+}
@@ -0,0 +1,60 @@
+// The MIT License (MIT)
+
+// Copyright (c) 2015 Y. T. Chung <zonyitoo@gmail.com>
+
+//  Permission is hereby granted, free of charge, to any person obtaining a
+//  copy of this software and associated documentation files (the "Software"),
+//  to deal in the Software without restriction, including without limitation
+//  the rights to use, copy, modify, merge, publish, distribute, sublicense,
+//  and/or sell copies of the Software, and to permit persons to whom the
+//  Software is furnished to do so, subject to the following conditions:
+//
+//  The above copyright notice and this permission notice shall be included in
+//  all copies or substantial portions of the Software.
+//
+//  THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+//  OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//  FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//  AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//  LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+//  FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+//  DEALINGS IN THE SOFTWARE.
+
+//! Coroutine-blocking synchronization primitives
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use runtime::EventSource;
+
+/// A multi-producer, single-consumer channel that parks the receiving
+/// coroutine until a value is sent
+pub struct Channel<T> {
+    queue: Mutex<VecDeque<T>>,
+}
+
+impl<T> Channel<T> {
+    /// Creates an empty channel
+    pub fn new() -> Channel<T> {
+        Channel { queue: Mutex::new(VecDeque::new()) }
+    }
+
+    /// Sends a value; a coroutine blocked in `recv` picks it up the next
+    /// time it polls the queue
+    pub fn send(&self, value: T) {
+        self.queue.lock().unwrap().push_back(value);
+    }
+
+    /// Blocks the calling coroutine until a value is available, then
+    /// returns it
+    pub fn recv(&self) -> T {
+        loop {
+            if let Some(value) = self.queue.lock().unwrap().pop_front() {
+                return value;
+            }
+
+            EventSource::park();
+            EventSource::yield_back();
+        }
+    }
+}
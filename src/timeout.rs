@@ -0,0 +1,90 @@
+// The MIT License (MIT)
+
+// Copyright (c) 2015 Y. T. Chung <zonyitoo@gmail.com>
+
+//  Permission is hereby granted, free of charge, to any person obtaining a
+//  copy of this software and associated documentation files (the "Software"),
+//  to deal in the Software without restriction, including without limitation
+//  the rights to use, copy, modify, merge, publish, distribute, sublicense,
+//  and/or sell copies of the Software, and to permit persons to whom the
+//  Software is furnished to do so, subject to the following conditions:
+//
+//  The above copyright notice and this permission notice shall be included in
+//  all copies or substantial portions of the Software.
+//
+//  THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+//  OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+//  FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+//  AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+//  LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+//  FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+//  DEALINGS IN THE SOFTWARE.
+
+//! Deadlines for blocking operations
+//!
+//! `with_timeout` arms a timer alongside whatever the wrapped closure is
+//! doing and races the two: if the closure reaches a scheduler yield point
+//! (a blocking `net` call, a `sync` wait, `sched()`, `sleep_ms`) after the
+//! timer has fired, it is unwound there exactly the way a cancelled
+//! `JoinHandle` would be, and `with_timeout` returns `Err(TimedOut)`
+//! instead of the closure's value. Whichever of the two finishes second is
+//! cancelled: a closure that wins has its timer's thread woken immediately
+//! rather than left sleeping out the rest of its duration.
+
+use std::any::Any;
+use std::panic;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use cancel::{Cancel, TIMEOUT_PANIC_MSG};
+use runtime::{self, Processor};
+
+/// The deadline passed to `with_timeout` elapsed before the wrapped
+/// operation finished
+#[derive(Debug)]
+pub struct TimedOut;
+
+/// Runs `f`, racing it against a `duration`-long timer
+///
+/// Returns `Ok` with `f`'s result if it finishes first, or `Err(TimedOut)`
+/// if the timer fires first and `f` is unwound at its next yield point. A
+/// `f` that never yields can't be interrupted, the same caveat that applies
+/// to `JoinHandle::cancel()`.
+///
+/// `F`'s `Send + 'static` bound comes straight from `std::thread::catch_panic`,
+/// which this builds on; it rules out wrapping a closure that borrows local
+/// state (reach for `net`'s own `read_timeout`/`write_timeout` there instead).
+pub fn with_timeout<F, T>(duration: Duration, f: F) -> Result<T, TimedOut>
+    where F: FnOnce() -> T + Send + 'static,
+          T: Send + 'static
+{
+    let deadline = Arc::new(Cancel::new());
+    let timer_deadline = deadline.clone();
+    let timer = runtime::timer::arm(duration, move || timer_deadline.request());
+
+    let previous = Processor::current().set_deadline(deadline);
+    let outcome = thread::catch_panic(f);
+    Processor::current().restore_deadline(previous);
+
+    timer.cancel();
+
+    match outcome {
+        Ok(value) => Ok(value),
+        Err(payload) => {
+            if is_timeout_panic(&payload) {
+                Err(TimedOut)
+            } else {
+                // panic!(payload) would box this payload a second time, which
+                // breaks scheduler::is_cancel_panic's downcast_ref::<&str>()
+                // for a cancellation caught in flight here — resume_unwind
+                // re-raises the original payload as-is.
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+}
+
+fn is_timeout_panic(payload: &Box<Any + Send>) -> bool {
+    payload.downcast_ref::<&str>().map_or(false, |msg| *msg == TIMEOUT_PANIC_MSG)
+}